@@ -1,27 +1,245 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
 use tokio::sync::mpsc::error::{SendError, TryRecvError, TrySendError};
 use tokio::sync::mpsc::Receiver;
 use tokio::sync::mpsc::Sender;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Error returned by [`BlockingRecv::blocking_recv_timeout`] when no value
+/// became available before the timeout elapsed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RecvTimeoutError;
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "timed out waiting for value")
+    }
+}
+
+impl std::error::Error for RecvTimeoutError {}
+
+/// Error returned by [`BlockingSend::blocking_send_timeout`]. Mirrors the shape
+/// of tokio's own `SendTimeoutError`, which the pinned 0.2 channel does not
+/// expose, and hands the un-sent message back to the caller in both cases.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SendTimeoutError<T> {
+    /// Capacity did not become available before the timeout elapsed.
+    Timeout(T),
+    /// The channel was closed.
+    Closed(T),
+}
+
+impl<T> fmt::Display for SendTimeoutError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendTimeoutError::Timeout(..) => write!(f, "timed out waiting for capacity"),
+            SendTimeoutError::Closed(..) => write!(f, "channel closed"),
+        }
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for SendTimeoutError<T> {}
+
+/// Drive `future` to completion on the current thread.
+///
+/// When called from a thread that already belongs to a multi-threaded Tokio
+/// runtime we offload the worker with [`block_in_place`] before driving the
+/// future, so the runtime keeps its timers and IO making progress while we
+/// block; driving the future directly on the foreign `futures` executor in
+/// that situation can deadlock. `block_in_place` is unsupported on a
+/// current-thread runtime (it panics), so we catch that panic and, like the
+/// no-runtime case, fall back to `futures::executor::block_on`.
+///
+/// Note that the fallback drives the future without a live Tokio timer, so
+/// futures that arm a tokio timer (e.g. the `blocking_*_timeout` variants)
+/// only work from inside a runtime — see their documentation.
+///
+/// [`block_in_place`]: tokio::task::block_in_place
+fn block_on<F: Future>(future: F) -> F::Output {
+    use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+
+    if tokio::runtime::Handle::try_current().is_ok() {
+        let mut future = Some(future);
+        let attempt = catch_unwind(AssertUnwindSafe(|| {
+            tokio::task::block_in_place(|| futures::executor::block_on(future.take().unwrap()))
+        }));
+        match attempt {
+            Ok(output) => return output,
+            Err(panic) => match future.take() {
+                // The future was never taken, so `block_in_place` itself
+                // panicked before running it: we are on a current-thread
+                // runtime. Drive the future on the plain executor instead.
+                Some(future) => return futures::executor::block_on(future),
+                // The future had already been taken, so the panic came from
+                // the future itself — propagate it rather than masking it.
+                None => resume_unwind(panic),
+            },
+        }
+    }
+
+    futures::executor::block_on(future)
+}
 
 pub trait BlockingRecv<T> {
     fn optimistic_blocking_recv(&mut self) -> Option<T>;
 
     fn blocking_recv(&mut self) -> Option<T>;
+
+    /// Like [`BlockingRecv::optimistic_blocking_recv`], but blocks for at most
+    /// `dur`. Returns `Ok(None)` when the channel is closed and
+    /// `Err(RecvTimeoutError)` when no value arrived in time.
+    ///
+    /// When no value is ready, the timeout arms a tokio timer, so this must be
+    /// called from a thread associated with a tokio runtime; outside a runtime
+    /// there is no timer to drive it and it panics unless a value (or the
+    /// close) is already available.
+    fn blocking_recv_timeout(&mut self, dur: Duration) -> Result<Option<T>, RecvTimeoutError>;
+
+    /// Optimistically drain up to `max` ready items into `buf`, blocking once
+    /// for a single value only if nothing is ready. Returns the number of
+    /// items pushed, which is `0` once the channel is closed and empty.
+    fn blocking_recv_many(&mut self, buf: &mut Vec<T>, max: usize) -> usize;
+
+    /// Returns a draining iterator that yields values via
+    /// [`BlockingRecv::optimistic_blocking_recv`] and terminates once the
+    /// channel is closed.
+    fn blocking_iter(&mut self) -> BlockingRecvIter<'_, T>
+    where
+        Self: Sized,
+    {
+        BlockingRecvIter { receiver: self }
+    }
+}
+
+/// Draining iterator over a blocking receiver, returned by
+/// [`BlockingRecv::blocking_iter`].
+pub struct BlockingRecvIter<'a, T> {
+    receiver: &'a mut dyn BlockingRecv<T>,
+}
+
+impl<'a, T> Iterator for BlockingRecvIter<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.optimistic_blocking_recv()
+    }
+}
+
+/// Primitives shared by the bounded and unbounded receivers, so the blocking
+/// adapters below need only one implementation each rather than a copy per
+/// channel kind.
+trait RawRecv<T> {
+    fn raw_try_recv(&mut self) -> Result<T, TryRecvError>;
+    fn raw_recv(&mut self) -> Pin<Box<dyn Future<Output = Option<T>> + '_>>;
+}
+
+impl<T> RawRecv<T> for Receiver<T> {
+    fn raw_try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.try_recv()
+    }
+
+    fn raw_recv(&mut self) -> Pin<Box<dyn Future<Output = Option<T>> + '_>> {
+        Box::pin(self.recv())
+    }
+}
+
+impl<T> RawRecv<T> for UnboundedReceiver<T> {
+    fn raw_try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.try_recv()
+    }
+
+    fn raw_recv(&mut self) -> Pin<Box<dyn Future<Output = Option<T>> + '_>> {
+        Box::pin(self.recv())
+    }
+}
+
+fn optimistic_blocking_recv<T>(rx: &mut impl RawRecv<T>) -> Option<T> {
+    match rx.raw_try_recv() {
+        Ok(value) => Some(value),
+        Err(TryRecvError::Empty) => block_on(rx.raw_recv()),
+        Err(TryRecvError::Closed) => None,
+    }
+}
+
+fn blocking_recv_timeout<T>(
+    rx: &mut impl RawRecv<T>,
+    dur: Duration,
+) -> Result<Option<T>, RecvTimeoutError> {
+    match rx.raw_try_recv() {
+        Ok(value) => Ok(Some(value)),
+        Err(TryRecvError::Closed) => Ok(None),
+        Err(TryRecvError::Empty) => block_on(async {
+            tokio::select! {
+                value = rx.raw_recv() => Ok(value),
+                _ = tokio::time::delay_for(dur) => Err(RecvTimeoutError),
+            }
+        }),
+    }
+}
+
+fn blocking_recv_many<T>(rx: &mut impl RawRecv<T>, buf: &mut Vec<T>, max: usize) -> usize {
+    let mut count = 0;
+    while count < max {
+        match rx.raw_try_recv() {
+            Ok(value) => {
+                buf.push(value);
+                count += 1;
+            }
+            Err(_) => break,
+        }
+    }
+    if count == 0 && max > 0 {
+        if let Some(value) = block_on(rx.raw_recv()) {
+            buf.push(value);
+            count += 1;
+        }
+    }
+    count
 }
 
 impl<T> BlockingRecv<T> for Receiver<T> {
     /// First try an optimistic `Receiver::try_recv`,
     /// the if value is unavailable, block until value is available and return it
     fn optimistic_blocking_recv(&mut self) -> Option<T> {
-        match self.try_recv() {
-            Ok(value) => Some(value),
-            Err(TryRecvError::Empty) => self.blocking_recv(),
-            Err(TryRecvError::Closed) => None,
-        }
+        optimistic_blocking_recv(self)
     }
 
     /// Blocks until value is available
     fn blocking_recv(&mut self) -> Option<T> {
-        futures::executor::block_on(self.recv())
+        block_on(self.recv())
+    }
+
+    fn blocking_recv_timeout(&mut self, dur: Duration) -> Result<Option<T>, RecvTimeoutError> {
+        blocking_recv_timeout(self, dur)
+    }
+
+    fn blocking_recv_many(&mut self, buf: &mut Vec<T>, max: usize) -> usize {
+        blocking_recv_many(self, buf, max)
+    }
+}
+
+impl<T> BlockingRecv<T> for UnboundedReceiver<T> {
+    /// First try an optimistic `UnboundedReceiver::try_recv`,
+    /// the if value is unavailable, block until value is available and return it
+    fn optimistic_blocking_recv(&mut self) -> Option<T> {
+        optimistic_blocking_recv(self)
+    }
+
+    /// Blocks until value is available
+    fn blocking_recv(&mut self) -> Option<T> {
+        block_on(self.recv())
+    }
+
+    fn blocking_recv_timeout(&mut self, dur: Duration) -> Result<Option<T>, RecvTimeoutError> {
+        blocking_recv_timeout(self, dur)
+    }
+
+    fn blocking_recv_many(&mut self, buf: &mut Vec<T>, max: usize) -> usize {
+        blocking_recv_many(self, buf, max)
     }
 }
 
@@ -29,6 +247,17 @@ pub trait BlockingSend<T> {
     fn optimistic_blocking_send(&mut self, message: T) -> Result<(), SendError<T>>;
 
     fn blocking_send(&mut self, message: T) -> Result<(), SendError<T>>;
+
+    /// Like [`BlockingSend::optimistic_blocking_send`], but blocks for at most
+    /// `dur`. Returns [`SendTimeoutError::Timeout`] when capacity did not
+    /// become available in time and [`SendTimeoutError::Closed`] when the
+    /// channel is closed.
+    ///
+    /// For the bounded [`Sender`], when no capacity is free the timeout arms a
+    /// tokio timer, so this must be called from a thread associated with a
+    /// tokio runtime; outside a runtime there is no timer to drive it and it
+    /// panics. [`UnboundedSender`] never waits and never consults the timeout.
+    fn blocking_send_timeout(&mut self, message: T, dur: Duration) -> Result<(), SendTimeoutError<T>>;
 }
 
 impl<T> BlockingSend<T> for Sender<T> {
@@ -41,7 +270,100 @@ impl<T> BlockingSend<T> for Sender<T> {
     }
 
     fn blocking_send(&mut self, message: T) -> Result<(), SendError<T>> {
-        futures::executor::block_on(self.send(message))
+        block_on(self.send(message))
+    }
+
+    fn blocking_send_timeout(
+        &mut self,
+        message: T,
+        dur: Duration,
+    ) -> Result<(), SendTimeoutError<T>> {
+        // Fast path: a slot is already free (or the channel is closed).
+        match self.try_send(message) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Closed(value)) => Err(SendTimeoutError::Closed(value)),
+            Err(TrySendError::Full(value)) => {
+                // tokio 0.2 has no `send_timeout`, so wait for capacity via
+                // `poll_ready` raced against a timer, keeping ownership of the
+                // message so we can hand it back on timeout or close.
+                let mut message = Some(value);
+                block_on(async {
+                    let ready = futures::future::poll_fn(|cx| self.poll_ready(cx));
+                    tokio::select! {
+                        ready = ready => ready
+                            .map_err(|_closed| SendTimeoutError::Closed(message.take().unwrap())),
+                        _ = tokio::time::delay_for(dur) =>
+                            Err(SendTimeoutError::Timeout(message.take().unwrap())),
+                    }?;
+                    match self.try_send(message.take().unwrap()) {
+                        Ok(()) => Ok(()),
+                        Err(TrySendError::Closed(value)) => Err(SendTimeoutError::Closed(value)),
+                        Err(TrySendError::Full(value)) => Err(SendTimeoutError::Timeout(value)),
+                    }
+                })
+            }
+        }
+    }
+}
+
+// Unbounded sends never block: every method maps directly to the non-blocking
+// `UnboundedSender::send`, and the timeout is simply never consulted.
+impl<T> BlockingSend<T> for UnboundedSender<T> {
+    fn optimistic_blocking_send(&mut self, message: T) -> Result<(), SendError<T>> {
+        self.send(message)
+    }
+
+    fn blocking_send(&mut self, message: T) -> Result<(), SendError<T>> {
+        self.send(message)
+    }
+
+    fn blocking_send_timeout(
+        &mut self,
+        message: T,
+        _dur: Duration,
+    ) -> Result<(), SendTimeoutError<T>> {
+        self.send(message)
+            .map_err(|SendError(value)| SendTimeoutError::Closed(value))
+    }
+}
+
+/// A reserved capacity slot on a [`Sender`], returned by
+/// [`BlockingReserve::blocking_reserve`]. Holding one guarantees that the
+/// following [`Permit::send`] will not have to wait for room in the channel.
+pub struct Permit<'a, T> {
+    sender: &'a mut Sender<T>,
+}
+
+impl<'a, T> Permit<'a, T> {
+    /// Send `message` into the slot reserved by this permit.
+    pub fn send(self, message: T) {
+        // The slot was reserved via `poll_ready`, so this send is guaranteed
+        // not to be `Full`; a `Closed` channel is the only way it can fail and
+        // there is nothing the caller can do with the message at that point.
+        let _ = self.sender.try_send(message);
+    }
+}
+
+pub trait BlockingReserve<T> {
+    fn optimistic_blocking_reserve(&mut self) -> Result<Permit<'_, T>, SendError<()>>;
+
+    fn blocking_reserve(&mut self) -> Result<Permit<'_, T>, SendError<()>>;
+}
+
+impl<T> BlockingReserve<T> for Sender<T> {
+    /// tokio 0.2 has no non-blocking `try_reserve`, so the optimistic path is
+    /// [`BlockingReserve::blocking_reserve`] itself, which returns immediately
+    /// when capacity is already available.
+    fn optimistic_blocking_reserve(&mut self) -> Result<Permit<'_, T>, SendError<()>> {
+        self.blocking_reserve()
+    }
+
+    /// Blocks until a capacity slot is reserved. `Sender::poll_ready` performs
+    /// the reservation in tokio 0.2: once it resolves, the next send is
+    /// guaranteed a slot.
+    fn blocking_reserve(&mut self) -> Result<Permit<'_, T>, SendError<()>> {
+        block_on(futures::future::poll_fn(|cx| self.poll_ready(cx))).map_err(|_closed| SendError(()))?;
+        Ok(Permit { sender: self })
     }
 }
 
@@ -97,4 +419,164 @@ mod test {
         .await
         .unwrap();
     }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn optimistic_blocking_unbounded() {
+        let (mut tx, mut rx) = mpsc::unbounded_channel();
+
+        tokio::task::spawn_blocking(move || {
+            for i in 0i32..10 {
+                tx.optimistic_blocking_send(i).unwrap();
+            }
+        })
+        .await
+        .unwrap();
+
+        tokio::task::spawn_blocking(move || {
+            for i in 0i32..10 {
+                let received = rx.optimistic_blocking_recv();
+                assert_eq!(received, Some(i));
+            }
+
+            assert_eq!(rx.optimistic_blocking_recv(), None);
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn blocking_unbounded() {
+        let (mut tx, mut rx) = mpsc::unbounded_channel();
+
+        tokio::task::spawn_blocking(move || {
+            for i in 0i32..10 {
+                tx.blocking_send(i).unwrap();
+            }
+        })
+        .await
+        .unwrap();
+
+        tokio::task::spawn_blocking(move || {
+            for i in 0i32..10 {
+                let received = rx.blocking_recv();
+                assert_eq!(received, Some(i));
+            }
+
+            assert_eq!(rx.blocking_recv(), None);
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn blocking_recv_timeout() {
+        let (mut tx, mut rx) = mpsc::channel(10);
+
+        tokio::task::spawn_blocking(move || {
+            tx.blocking_send(1).unwrap();
+
+            assert_eq!(
+                rx.blocking_recv_timeout(Duration::from_secs(1)),
+                Ok(Some(1))
+            );
+            assert_eq!(
+                rx.blocking_recv_timeout(Duration::from_millis(10)),
+                Err(RecvTimeoutError)
+            );
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn blocking_send_timeout() {
+        let (mut tx, mut rx) = mpsc::channel(1);
+
+        tokio::task::spawn_blocking(move || {
+            assert!(tx.blocking_send_timeout(1, Duration::from_secs(1)).is_ok());
+
+            match tx.blocking_send_timeout(2, Duration::from_millis(10)) {
+                Err(SendTimeoutError::Timeout(2)) => {}
+                other => panic!("expected timeout, got {:?}", other),
+            }
+
+            assert_eq!(rx.blocking_recv(), Some(1));
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn blocking_reserve() {
+        let (mut tx, mut rx) = mpsc::channel(10);
+
+        tokio::task::spawn_blocking(move || {
+            for i in 0i32..10 {
+                let permit = tx.optimistic_blocking_reserve().unwrap();
+                permit.send(i);
+            }
+        })
+        .await
+        .unwrap();
+
+        tokio::task::spawn_blocking(move || {
+            for i in 0i32..10 {
+                assert_eq!(rx.blocking_recv(), Some(i));
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn blocking_iter() {
+        let (mut tx, mut rx) = mpsc::channel(10);
+
+        tokio::task::spawn_blocking(move || {
+            for i in 0i32..10 {
+                tx.blocking_send(i).unwrap();
+            }
+        })
+        .await
+        .unwrap();
+
+        tokio::task::spawn_blocking(move || {
+            let received: Vec<i32> = rx.blocking_iter().collect();
+            assert_eq!(received, (0i32..10).collect::<Vec<_>>());
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn blocking_recv_many() {
+        let (mut tx, mut rx) = mpsc::channel(10);
+
+        tokio::task::spawn_blocking(move || {
+            for i in 0i32..10 {
+                tx.blocking_send(i).unwrap();
+            }
+        })
+        .await
+        .unwrap();
+
+        tokio::task::spawn_blocking(move || {
+            let mut buf = Vec::new();
+
+            // `max == 0` must neither block nor push anything.
+            assert_eq!(rx.blocking_recv_many(&mut buf, 0), 0);
+            assert!(buf.is_empty());
+
+            let count = rx.blocking_recv_many(&mut buf, 4);
+            assert_eq!(count, 4);
+            assert_eq!(buf, vec![0, 1, 2, 3]);
+
+            buf.clear();
+            let count = rx.blocking_recv_many(&mut buf, 100);
+            assert_eq!(count, 6);
+            assert_eq!(buf, vec![4, 5, 6, 7, 8, 9]);
+        })
+        .await
+        .unwrap();
+    }
 }